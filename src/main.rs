@@ -18,23 +18,35 @@
 //! This program is homework for "Simulation of Biology", a remarkable course
 //! by Greg Turk of Georgia Tech: <http://www.cc.gatech.edu/~turk/bio_sim/>.
 
-// One obvious way to optimize this would be to store (in the grid) which cells
-// are adjacent to green cells, rather than calling is_adjacent every time we
-// want to know.
-
-extern crate graphics;
-extern crate piston;
-extern crate glutin_window;
-extern crate opengl_graphics;
+// Rather than scanning a walker's neighbors on every step to see whether it
+// has reached the aggregate, the grid keeps an explicit `frontier` set: every
+// empty cell bordering an occupied one. `set` maintains it incrementally, so a
+// walker sticks with a single flag test.
+
 extern crate rand;
+extern crate noise;
+
+// The native backend draws with Piston/OpenGL; none of this is pulled in when
+// building for the web, so the DLA core has no dependency on OpenGL there.
+#[cfg(not(target_arch = "wasm32"))] extern crate graphics;
+#[cfg(not(target_arch = "wasm32"))] extern crate piston;
+#[cfg(not(target_arch = "wasm32"))] extern crate glutin_window;
+#[cfg(not(target_arch = "wasm32"))] extern crate opengl_graphics;
+
+// The wasm backend blits into an RGBA buffer and drives the frame loop from
+// requestAnimationFrame.
+#[cfg(target_arch = "wasm32")] extern crate wasm_bindgen;
+#[cfg(target_arch = "wasm32")] extern crate web_sys;
 
 use rand::Rng;
-use piston::window::WindowSettings;
-use piston::event_loop::*;
-use piston::input::*;
-use graphics::*;
-use opengl_graphics::{GlGraphics, OpenGL};
-use glutin_window::GlutinWindow as Window;
+use noise::{NoiseFn, OpenSimplex, Seedable};
+
+#[cfg(not(target_arch = "wasm32"))] use piston::window::WindowSettings;
+#[cfg(not(target_arch = "wasm32"))] use piston::event_loop::*;
+#[cfg(not(target_arch = "wasm32"))] use piston::input::*;
+#[cfg(not(target_arch = "wasm32"))] use graphics::*;
+#[cfg(not(target_arch = "wasm32"))] use opengl_graphics::{GlGraphics, OpenGL};
+#[cfg(not(target_arch = "wasm32"))] use glutin_window::GlutinWindow as Window;
 
 pub const WINDOW_HEIGHT: u32 = 960;
 pub const WINDOW_WIDTH: u32 = 1280;
@@ -46,22 +58,84 @@ pub const GRID_HEIGHT: usize = (WINDOW_HEIGHT / BLOCK_SIZE) as usize;
 
 pub const FRAME_DURATION: f64 = 0.1; // seconds
 
+/// How far beyond the current aggregate radius walkers are launched.
+pub const LAUNCH_MARGIN: f64 = 5.0;
+
+/// The colors of the simulation, shared by every backend. RGBA, components in
+/// 0.0 ..= 1.0.
+const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const GREEN: [f32; 4] = [0.0, 0.4, 0.0, 1.0];
+const GRAY:  [f32; 4] = [0.3, 0.3, 0.3, 1.0];
+const BLACK: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+/// The eight neighbors of a cell, used by both DLA and Life.
+const DIRS: [(i32, i32); 8] = [
+    (-1, -1), ( 0, -1), ( 1, -1),
+    (-1,  0),           ( 1,  0),
+    (-1,  1), ( 0,  1), ( 1,  1)];
+
+/// The eight neighbors in clockwise order, used to trace region outlines by
+/// Moore-neighbor boundary following.
+const CW: [(i32, i32); 8] = [
+    (-1,  0), (-1, -1), ( 0, -1), ( 1, -1),
+    ( 1,  0), ( 1,  1), ( 0,  1), (-1,  1)];
+
+/// Which simulation rule set `update_one_frame` applies.
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    /// Diffusion-limited aggregation: random walkers stick to the aggregate.
+    Dla,
+    /// Conway's Game of Life: synchronous birth/survival/death.
+    Life
+}
+
+/// A connected cluster of occupied cells, as found by `Grid::regions`.
+struct Region {
+    id: usize,
+    cells: Vec<(i32, i32)>,
+    /// A closed, smoothed boundary polygon in grid coordinates.
+    outline: Vec<(f64, f64)>
+}
+
 struct Grid<R: Rng> {
     cells: Vec<bool>,
+    walls: Vec<bool>,
+    /// Every empty cell that borders an occupied cell. Maintained incrementally
+    /// by `set` so a walker can "stick" with a single flag test rather than
+    /// scanning its neighbors on every step.
+    frontier: Vec<bool>,
+    /// Cell the aggregate grows from; walkers launch on a circle around it.
+    center: (i32, i32),
+    /// Distance of the farthest occupied cell from `center`.
+    max_radius: f64,
     t: f64,
     rng: R,
     stickiness: f64,
-    running: bool
+    mode: Mode,
+    show_regions: bool,
+    running: bool,
+    /// Spatial frequency at which `noise_grid` samples the OpenSimplex field.
+    noise_frequency: f64,
+    /// Cells whose noise value exceeds this threshold become seeds.
+    noise_threshold: f64
 }
 
 impl<R: Rng> Grid<R> {
     fn new_empty(rng: R, stickiness: f64) -> Grid<R> {
         Grid {
             cells: vec![false; GRID_WIDTH * GRID_HEIGHT],
+            walls: vec![false; GRID_WIDTH * GRID_HEIGHT],
+            frontier: vec![false; GRID_WIDTH * GRID_HEIGHT],
+            center: (GRID_WIDTH as i32 / 2, GRID_HEIGHT as i32 / 2),
+            max_radius: 0.0,
             t: 0.0,
             rng: rng,
             stickiness: stickiness,
-            running: true
+            mode: Mode::Dla,
+            show_regions: false,
+            running: true,
+            noise_frequency: 0.05,
+            noise_threshold: 0.2
         }
     }
 
@@ -71,6 +145,13 @@ impl<R: Rng> Grid<R> {
         grid
     }
 
+    /// Consume the grid and hand back its RNG, so a fresh grid can continue the
+    /// same random sequence across a reset.
+    #[cfg(target_arch = "wasm32")]
+    fn into_rng(self) -> R {
+        self.rng
+    }
+
     fn in_bounds(&self, x: i32, y: i32) -> bool {
         0 < x &&
         x < GRID_WIDTH as i32 &&
@@ -82,24 +163,80 @@ impl<R: Rng> Grid<R> {
         self.in_bounds(x, y) && self.cells[y as usize * GRID_WIDTH + x as usize]
     }
 
-    /// True if the given cell (x, y) is adjacent to any occupied cell.
-    fn is_adjacent(&self, x: i32, y: i32) -> bool {
-           self.test(x - 1, y - 1)
-        || self.test(x    , y - 1)
-        || self.test(x + 1, y - 1)
-        || self.test(x - 1, y    )
-        || self.test(x + 1, y    )
-        || self.test(x - 1, y + 1)
-        || self.test(x    , y + 1)
-        || self.test(x + 1, y + 1)
+    /// True if (x, y) is an empty cell bordering the aggregate, i.e. a place a
+    /// walker can stick. This is just a flag test — the frontier is kept up to
+    /// date by `set`.
+    fn is_frontier(&self, x: i32, y: i32) -> bool {
+        self.in_bounds(x, y) && self.frontier[y as usize * GRID_WIDTH + x as usize]
     }
 
     fn set(&mut self, x: i32, y: i32) {
-        self.cells[y as usize * GRID_WIDTH + x as usize] = true;
+        let i = y as usize * GRID_WIDTH + x as usize;
+        if self.cells[i] {
+            return;
+        }
+        self.cells[i] = true;
+        self.frontier[i] = false;
+
+        // Every empty in-bounds neighbor now borders the aggregate.
+        for &(dx, dy) in DIRS.iter() {
+            let (nx, ny) = (x + dx, y + dy);
+            if self.in_bounds(nx, ny) {
+                let ni = ny as usize * GRID_WIDTH + nx as usize;
+                if !self.cells[ni] {
+                    self.frontier[ni] = true;
+                }
+            }
+        }
+
+        let (cx, cy) = self.center;
+        let d = (((x - cx) as f64).powi(2) + ((y - cy) as f64).powi(2)).sqrt();
+        if d > self.max_radius {
+            self.max_radius = d;
+        }
+    }
+
+    /// Mark (x, y) as an immovable wall: walkers stick to it like any other
+    /// occupied cell, but it renders in a distinct color.
+    fn set_wall(&mut self, x: i32, y: i32) {
+        self.set(x, y);
+        self.walls[y as usize * GRID_WIDTH + x as usize] = true;
     }
 
-    fn update(&mut self, args: &UpdateArgs) {
-	self.t += args.dt;
+    /// Paint a line of cells from (x0, y0) to (x1, y1) using the integer
+    /// Bresenham algorithm, so fast mouse drags don't leave gaps. `wall`
+    /// selects whether the painted cells are ordinary seeds or walls.
+    fn draw_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, wall: bool) {
+        let dx = (x1 - x0).abs();
+        let dy = -(y1 - y0).abs();
+        let sx = if x0 < x1 { 1 } else { -1 };
+        let sy = if y0 < y1 { 1 } else { -1 };
+        let mut err = dx + dy;
+        let (mut x, mut y) = (x0, y0);
+        loop {
+            if self.in_bounds(x, y) {
+                if wall { self.set_wall(x, y); } else { self.set(x, y); }
+            }
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// Advance the simulation by `dt` seconds, stepping whole frames as the
+    /// accumulated time crosses `FRAME_DURATION`. Takes a plain `dt` rather than
+    /// a windowing type so the core stays backend-agnostic.
+    fn update(&mut self, dt: f64) {
+	self.t += dt;
 	while self.t > FRAME_DURATION {
 	    self.update_one_frame();
 	    self.t -= FRAME_DURATION;
@@ -107,16 +244,37 @@ impl<R: Rng> Grid<R> {
     }
 
     fn update_one_frame(&mut self) {
-        const DIRS: [(i32, i32); 8] = [
-            (-1, -1), ( 0, -1), ( 1, -1),
-            (-1,  0),           ( 1,  0),
-            (-1,  1), ( 0,  1), ( 1,  1)];
+        match self.mode {
+            Mode::Dla => self.step_dla(),
+            Mode::Life => self.step_life()
+        }
+    }
 
+    fn step_dla(&mut self) {
+        use std::f64::consts::PI;
+        let (cx, cy) = self.center;
+        // Keep the launch circle inside the grid. When the aggregate (or a
+        // scattered seed from `noise_grid`/a far-flung mouse edit) pushes
+        // `max_radius + LAUNCH_MARGIN` past the largest circle that fits inside
+        // the grid, clamp it to that circle so launch points still land on the
+        // grid.
+        let max_launch = (GRID_WIDTH.min(GRID_HEIGHT) as f64) / 2.0 - 1.0;
+        let launch = f64::min(self.max_radius + LAUNCH_MARGIN, max_launch);
+        let kill = f64::max(2.0 * self.max_radius, launch + LAUNCH_MARGIN);
         for _ in 0 .. 60 {
-            let mut x = self.rng.gen_range(0, GRID_WIDTH as i32);
-            let mut y = self.rng.gen_range(0, GRID_HEIGHT as i32);
+            let a = self.rng.gen::<f64>() * 2.0 * PI;
+            let lx = (cx as f64 + launch * a.cos()).round() as i32;
+            let ly = (cy as f64 + launch * a.sin()).round() as i32;
+            // If the point still falls off the grid, spawn uniformly instead of
+            // letting the walker die on its first `in_bounds` check.
+            let (mut x, mut y) = if self.in_bounds(lx, ly) {
+                (lx, ly)
+            } else {
+                (self.rng.gen_range(1, GRID_WIDTH as i32),
+                 self.rng.gen_range(1, GRID_HEIGHT as i32))
+            };
             loop {
-                if self.is_adjacent(x, y) && self.rng.gen::<f64>() < self.stickiness {
+                if self.is_frontier(x, y) && self.rng.gen::<f64>() < self.stickiness {
                     self.set(x, y);
                     break;
                 }
@@ -126,24 +284,281 @@ impl<R: Rng> Grid<R> {
                 if !self.in_bounds(x, y) {
                     break;
                 }
+                let d = (((x - cx) as f64).powi(2) + ((y - cy) as f64).powi(2)).sqrt();
+                if d > kill {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Count the live cells among the 8 neighbors of (x, y).
+    fn count_neighbors(&self, x: i32, y: i32) -> u32 {
+        let mut n = 0;
+        for &(dx, dy) in DIRS.iter() {
+            if self.test(x + dx, y + dy) {
+                n += 1;
+            }
+        }
+        n
+    }
+
+    /// Advance one generation of Conway's Game of Life. Life is synchronous, so
+    /// the next generation is built into a fresh buffer and swapped in rather
+    /// than mutating `cells` in place.
+    fn step_life(&mut self) {
+        let mut next = vec![false; GRID_WIDTH * GRID_HEIGHT];
+        for y in 0 .. GRID_HEIGHT as i32 {
+            for x in 0 .. GRID_WIDTH as i32 {
+                let n = self.count_neighbors(x, y);
+                let alive = if self.test(x, y) {
+                    n == 2 || n == 3
+                } else {
+                    n == 3
+                };
+                next[y as usize * GRID_WIDTH + x as usize] = alive;
+            }
+        }
+        self.cells = next;
+    }
+
+    /// Find the connected clusters of occupied cells using an 8-connected
+    /// flood fill, returning one `Region` per cluster with its member cells
+    /// and a smoothed boundary polygon.
+    fn regions(&self) -> Vec<Region> {
+        let mut label: Vec<i32> = vec![-1; GRID_WIDTH * GRID_HEIGHT];
+        let mut regions = Vec::new();
+
+        for y in 0 .. GRID_HEIGHT as i32 {
+            for x in 0 .. GRID_WIDTH as i32 {
+                let i = y as usize * GRID_WIDTH + x as usize;
+                if !self.cells[i] || label[i] >= 0 {
+                    continue;
+                }
+
+                // Flood fill this component.
+                let id = regions.len();
+                let mut members = Vec::new();
+                let mut stack = vec![(x, y)];
+                label[i] = id as i32;
+                while let Some((cx, cy)) = stack.pop() {
+                    members.push((cx, cy));
+                    for &(dx, dy) in DIRS.iter() {
+                        let (nx, ny) = (cx + dx, cy + dy);
+                        if self.test(nx, ny) {
+                            let ni = ny as usize * GRID_WIDTH + nx as usize;
+                            if label[ni] < 0 {
+                                label[ni] = id as i32;
+                                stack.push((nx, ny));
+                            }
+                        }
+                    }
+                }
+
+                let outline = smooth(&self.trace_outline(&label, id as i32, (x, y)));
+                regions.push(Region { id: id, cells: members, outline: outline });
+            }
+        }
+
+        regions
+    }
+
+    /// Trace the outline of the region labelled `id` by Moore-neighbor
+    /// boundary following, starting from its topmost-leftmost cell `start`.
+    /// Returns a closed loop of cell coordinates.
+    fn trace_outline(&self, label: &[i32], id: i32, start: (i32, i32)) -> Vec<(i32, i32)> {
+        let in_region = |p: (i32, i32)| {
+            self.in_bounds(p.0, p.1) && label[p.1 as usize * GRID_WIDTH + p.0 as usize] == id
+        };
+
+        let mut boundary = vec![start];
+        let mut p = start;
+        // We reached `start` from the west, which is background by construction.
+        let mut back = (start.0 - 1, start.1);
+        let limit = 8 * (boundary.capacity() + 1) + GRID_WIDTH * GRID_HEIGHT;
+
+        loop {
+            let bdir = (back.0 - p.0, back.1 - p.1);
+            let start_idx = match CW.iter().position(|&d| d == bdir) {
+                Some(k) => k,
+                None => break
+            };
+
+            let mut found = false;
+            for k in 1 .. 9 {
+                let idx = (start_idx + k) % 8;
+                let (dx, dy) = CW[idx];
+                let c = (p.0 + dx, p.1 + dy);
+                if in_region(c) {
+                    let (pdx, pdy) = CW[(start_idx + k - 1) % 8];
+                    back = (p.0 + pdx, p.1 + pdy);
+                    p = c;
+                    found = true;
+                    break;
+                }
             }
+
+            if !found || p == start || boundary.len() > limit {
+                break;
+            }
+            boundary.push(p);
         }
+
+        boundary
     }
 }
 
-fn render<R: Rng>(grid: &Grid<R>, gl: &mut GlGraphics, args: &RenderArgs) {
-    const WHITE:  [f32; 4] = [1.0, 1.0, 1.0, 1.0];
-    const GREEN: [f32; 4] = [0.0, 0.4, 0.0, 1.0];
+/// Smooth a closed polygon by replacing each point with the average of itself
+/// and its two neighbors on each side (a 5-point window that wraps around).
+/// A polygon of fewer than five points is returned unsmoothed. Every point is
+/// shifted by half a cell so it sits at the center of its cell.
+fn smooth(points: &[(i32, i32)]) -> Vec<(f64, f64)> {
+    let n = points.len();
+    if n < 5 {
+        return points.iter().map(|&(x, y)| (x as f64 + 0.5, y as f64 + 0.5)).collect();
+    }
+    let mut out = Vec::with_capacity(n);
+    for i in 0 .. n {
+        let mut sx = 0.0;
+        let mut sy = 0.0;
+        for k in 0 .. 5 {
+            let (x, y) = points[(i + n - 2 + k) % n];
+            sx += x as f64;
+            sy += y as f64;
+        }
+        out.push((sx / 5.0 + 0.5, sy / 5.0 + 0.5));
+    }
+    out
+}
+
+/// A stable, distinct color for a region derived from its id.
+fn region_color(id: usize) -> [f32; 4] {
+    // Offset the id so region 0 doesn't hash to BLACK, the outline color.
+    let h = (id + 1).wrapping_mul(2654435761) as u32;
+    [
+        ((h & 0xff) as f32) / 255.0,
+        (((h >> 8) & 0xff) as f32) / 255.0,
+        (((h >> 16) & 0xff) as f32) / 255.0,
+        1.0
+    ]
+}
+
+/// The color a cell should be drawn in: its region color when region analysis
+/// is on, otherwise gray for walls and green for ordinary occupied cells.
+#[cfg(target_arch = "wasm32")]
+fn cell_color<R: Rng>(grid: &Grid<R>, x: i32, y: i32, labels: &Option<Vec<i32>>) -> [f32; 4] {
+    if let Some(ref labels) = *labels {
+        let id = labels[y as usize * GRID_WIDTH + x as usize];
+        if id >= 0 {
+            return region_color(id as usize);
+        }
+    }
+    if grid.walls[y as usize * GRID_WIDTH + x as usize] {
+        GRAY
+    } else {
+        GREEN
+    }
+}
+
+/// A rendering/windowing backend. The native Piston backend is the default; the
+/// wasm backend blits to a canvas. The `Grid` logic is shared across both.
+trait Backend<R: Rng> {
+    /// Draw the current grid state to the display.
+    fn present(&mut self, grid: &Grid<R>);
+}
+
+/// Tracks a mouse-painting gesture so that both backends turn clicks and drags
+/// into the same seed/wall edits. `painting` is `Some(is_wall)` while a button
+/// is held; `last_cell` is the previous grid cell so drags draw a continuous
+/// Bresenham line rather than isolated dots.
+struct Painter {
+    painting: Option<bool>,
+    last_cell: Option<(i32, i32)>
+}
+
+impl Painter {
+    fn new() -> Painter {
+        Painter { painting: None, last_cell: None }
+    }
+
+    /// Convert pixel coordinates to a grid cell.
+    fn cell(px: f64, py: f64) -> (i32, i32) {
+        (px as i32 / BLOCK_SIZE as i32, py as i32 / BLOCK_SIZE as i32)
+    }
+
+    fn paint<R: Rng>(grid: &mut Grid<R>, x: i32, y: i32, wall: bool) {
+        if grid.in_bounds(x, y) {
+            if wall { grid.set_wall(x, y); } else { grid.set(x, y); }
+        }
+    }
 
-    gl.draw(args.viewport(), |c, gl| {
+    /// Begin a gesture at the given pixel position. `wall` paints immovable
+    /// walls instead of ordinary seeds.
+    fn press<R: Rng>(&mut self, grid: &mut Grid<R>, px: f64, py: f64, wall: bool) {
+        let (cx, cy) = Painter::cell(px, py);
+        Painter::paint(grid, cx, cy, wall);
+        self.painting = Some(wall);
+        self.last_cell = Some((cx, cy));
+    }
+
+    /// Extend the gesture to a new pixel position, drawing a line from the
+    /// previous cell so fast drags don't leave gaps.
+    fn motion<R: Rng>(&mut self, grid: &mut Grid<R>, px: f64, py: f64) {
+        if let Some(wall) = self.painting {
+            let (cx, cy) = Painter::cell(px, py);
+            match self.last_cell {
+                Some((lx, ly)) => grid.draw_line(lx, ly, cx, cy, wall),
+                None => Painter::paint(grid, cx, cy, wall)
+            }
+            self.last_cell = Some((cx, cy));
+        }
+    }
+
+    fn release(&mut self) {
+        self.painting = None;
+        self.last_cell = None;
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn render<R: Rng>(grid: &Grid<R>, gl: &mut GlGraphics, viewport: Viewport) {
+    let regions = if grid.show_regions {
+        Some(grid.regions())
+    } else {
+        None
+    };
+
+    gl.draw(viewport, |c, gl| {
 	graphics::clear(WHITE, gl);
 	let tr = c.transform.scale(BLOCK_SIZE as f64, BLOCK_SIZE as f64);
 
+        if let Some(ref regions) = regions {
+            for region in regions {
+                let color = region_color(region.id);
+                for &(x, y) in region.cells.iter() {
+                    let coords = [x as f64, y as f64, 1.0, 1.0];
+                    rectangle(color, coords, tr, gl);
+                }
+                let n = region.outline.len();
+                for i in 0 .. n {
+                    let (x0, y0) = region.outline[i];
+                    let (x1, y1) = region.outline[(i + 1) % n];
+                    line(BLACK, 0.5, [x0, y0, x1, y1], tr, gl);
+                }
+            }
+            return;
+        }
+
         for y in 0 .. GRID_HEIGHT as i32 {
             for x in 0 .. GRID_WIDTH as i32 {
                 if grid.test(x, y) {
+                    let color = if grid.walls[y as usize * GRID_WIDTH + x as usize] {
+                        GRAY
+                    } else {
+                        GREEN
+                    };
                     let coords = [x as f64, y as f64, 1.0, 1.0];
-                    rectangle(GREEN, coords, tr, gl);
+                    rectangle(color, coords, tr, gl);
                 }
             }
         }
@@ -169,6 +584,53 @@ fn odd_grid<R: Rng>(rng: R) -> Grid<R> {
     grid
 }
 
+fn life<R: Rng>(rng: R) -> Grid<R> {
+    let mut grid = Grid::new_empty(rng, 0.1);
+    grid.mode = Mode::Life;
+    for i in 0 .. GRID_WIDTH * GRID_HEIGHT {
+        grid.cells[i] = grid.rng.gen::<f64>() < 0.25;
+    }
+    grid
+}
+
+/// Seed the aggregate from a procedural OpenSimplex noise field instead of a
+/// single pixel. Every cell whose noise value exceeds `noise_threshold` is set,
+/// giving scattered organic clusters that the DLA then grows between. The noise
+/// function is seeded from the grid's RNG so runs are reproducible.
+fn noise_grid<R: Rng>(rng: R) -> Grid<R> {
+    let mut grid = Grid::new_empty(rng, 0.1);
+    let seed: u32 = grid.rng.gen();
+    let noise = OpenSimplex::new().set_seed(seed);
+    let freq = grid.noise_frequency;
+    let threshold = grid.noise_threshold;
+    for y in 0 .. GRID_HEIGHT as i32 {
+        for x in 0 .. GRID_WIDTH as i32 {
+            let v = noise.get([x as f64 * freq, y as f64 * freq]);
+            if v > threshold {
+                grid.set(x, y);
+            }
+        }
+    }
+    grid
+}
+
+/// The native backend: a Piston window rendered with OpenGL.
+#[cfg(not(target_arch = "wasm32"))]
+struct PistonBackend {
+    gl: GlGraphics,
+    viewport: Option<Viewport>
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<R: Rng> Backend<R> for PistonBackend {
+    fn present(&mut self, grid: &Grid<R>) {
+        if let Some(viewport) = self.viewport {
+            render(grid, &mut self.gl, viewport);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
 fn main() {
     // Change this to OpenGL::V2_1 if not working.
     let opengl = OpenGL::V3_2;
@@ -183,20 +645,25 @@ fn main() {
 	.build()
 	.unwrap();
 
-    let mut gl = GlGraphics::new(opengl);
+    let mut backend = PistonBackend { gl: GlGraphics::new(opengl), viewport: None };
 
     // Fast XorShift random number generator, seeded from a better (but slower)
     // source of randomness.
     let rng: rand::XorShiftRng = rand::random();
     let mut grid = Grid::new(rng, 0.1);
 
+    let mut cursor = [0.0, 0.0];
+    let mut painter = Painter::new();
+
     for e in window.events() {
 	match e {
-	    Event::Render(ref r) =>
-                render(&grid, &mut gl, r),
+	    Event::Render(ref r) => {
+                backend.viewport = Some(r.viewport());
+                backend.present(&grid);
+            },
 	    Event::Update(ref u) =>
 		if grid.running {
-		    grid.update(u);
+		    grid.update(u.dt);
 		},
 	    Event::Input(Input::Press(Button::Keyboard(Key::Space))) =>
                 grid.running = !grid.running,
@@ -208,7 +675,267 @@ fn main() {
                 grid = Grid::new(grid.rng, 0.01),
 	    Event::Input(Input::Press(Button::Keyboard(Key::D0))) =>
                 grid = odd_grid(grid.rng),
+	    Event::Input(Input::Press(Button::Keyboard(Key::L))) =>
+                grid = life(grid.rng),
+	    Event::Input(Input::Press(Button::Keyboard(Key::N))) =>
+                grid = noise_grid(grid.rng),
+	    Event::Input(Input::Press(Button::Keyboard(Key::R))) =>
+                grid.show_regions = !grid.show_regions,
+	    Event::Input(Input::Move(Motion::MouseCursor(mx, my))) => {
+                cursor = [mx, my];
+                painter.motion(&mut grid, mx, my);
+            },
+	    Event::Input(Input::Press(Button::Mouse(button))) => {
+                let wall = button == MouseButton::Right;
+                if button == MouseButton::Left || wall {
+                    painter.press(&mut grid, cursor[0], cursor[1], wall);
+                }
+            },
+	    Event::Input(Input::Release(Button::Mouse(_))) =>
+                painter.release(),
 	    _ => {}
 	}
     }
 }
+
+// --- WebAssembly backend ---------------------------------------------------
+//
+// On wasm there is no OpenGL: we blit the grid into an RGBA pixel buffer and
+// draw it to a 2D canvas, driving the frame loop from requestAnimationFrame.
+// The `Grid` core, `Painter`, the `Backend` trait and the grid generators
+// above are shared with the native backend.
+
+#[cfg(target_arch = "wasm32")] use std::rc::Rc;
+#[cfg(target_arch = "wasm32")] use std::cell::RefCell;
+#[cfg(target_arch = "wasm32")] use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")] use wasm_bindgen::{Clamped, JsCast};
+
+/// The concrete RNG used on the web, matching the native backend.
+#[cfg(target_arch = "wasm32")]
+type WasmRng = rand::XorShiftRng;
+
+/// Render the grid into `buf` as tightly packed RGBA, one `BLOCK_SIZE` square
+/// per cell. `buf` must be `WINDOW_WIDTH * WINDOW_HEIGHT * 4` bytes long.
+#[cfg(target_arch = "wasm32")]
+fn blit<R: Rng>(grid: &Grid<R>, buf: &mut [u8]) {
+    let regions = if grid.show_regions { Some(grid.regions()) } else { None };
+    // A per-cell region id, so coloring a pixel is a lookup rather than a
+    // linear scan of every region's member list.
+    let labels = regions.as_ref().map(|regions| {
+        let mut labels = vec![-1i32; GRID_WIDTH * GRID_HEIGHT];
+        for region in regions {
+            for &(x, y) in region.cells.iter() {
+                labels[y as usize * GRID_WIDTH + x as usize] = region.id as i32;
+            }
+        }
+        labels
+    });
+    let bytes = |c: [f32; 4]| [(c[0] * 255.0) as u8, (c[1] * 255.0) as u8, (c[2] * 255.0) as u8, 255];
+
+    let white = bytes(WHITE);
+    for px in buf.chunks_mut(4) {
+        px.copy_from_slice(&white);
+    }
+
+    let bs = BLOCK_SIZE as usize;
+    for y in 0 .. GRID_HEIGHT as i32 {
+        for x in 0 .. GRID_WIDTH as i32 {
+            if !grid.test(x, y) {
+                continue;
+            }
+            let color = bytes(cell_color(grid, x, y, &labels));
+            for dy in 0 .. bs {
+                for dx in 0 .. bs {
+                    let px = x as usize * bs + dx;
+                    let py = y as usize * bs + dy;
+                    let i = (py * WINDOW_WIDTH as usize + px) * 4;
+                    buf[i .. i + 4].copy_from_slice(&color);
+                }
+            }
+        }
+    }
+
+    // Stroke each region's smoothed outline, matching the native render mode.
+    if let Some(ref regions) = regions {
+        let black = bytes(BLACK);
+        let scale = BLOCK_SIZE as f64;
+        for region in regions {
+            let n = region.outline.len();
+            for i in 0 .. n {
+                let (x0, y0) = region.outline[i];
+                let (x1, y1) = region.outline[(i + 1) % n];
+                stroke_line(buf, &black, x0 * scale, y0 * scale, x1 * scale, y1 * scale);
+            }
+        }
+    }
+}
+
+/// Draw a one-pixel Bresenham line into the RGBA buffer, clipping to bounds.
+#[cfg(target_arch = "wasm32")]
+fn stroke_line(buf: &mut [u8], color: &[u8; 4], x0: f64, y0: f64, x1: f64, y1: f64) {
+    let (mut x, mut y) = (x0.round() as i32, y0.round() as i32);
+    let (x1, y1) = (x1.round() as i32, y1.round() as i32);
+    let dx = (x1 - x).abs();
+    let dy = -(y1 - y).abs();
+    let sx = if x < x1 { 1 } else { -1 };
+    let sy = if y < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    loop {
+        if x >= 0 && x < WINDOW_WIDTH as i32 && y >= 0 && y < WINDOW_HEIGHT as i32 {
+            let i = (y as usize * WINDOW_WIDTH as usize + x as usize) * 4;
+            buf[i .. i + 4].copy_from_slice(color);
+        }
+        if x == x1 && y == y1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+/// The wasm backend: an RGBA buffer painted to a 2D canvas.
+#[cfg(target_arch = "wasm32")]
+struct CanvasBackend {
+    ctx: web_sys::CanvasRenderingContext2d,
+    buf: Vec<u8>
+}
+
+#[cfg(target_arch = "wasm32")]
+impl<R: Rng> Backend<R> for CanvasBackend {
+    fn present(&mut self, grid: &Grid<R>) {
+        blit(grid, &mut self.buf);
+        let data = web_sys::ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(&mut self.buf[..]), WINDOW_WIDTH, WINDOW_HEIGHT).unwrap();
+        self.ctx.put_image_data(&data, 0.0, 0.0).unwrap();
+    }
+}
+
+/// The web application: the shared `Grid`, mouse state, and canvas backend. The
+/// grid is kept in an `Option` so a reset can move its RNG into the replacement.
+#[cfg(target_arch = "wasm32")]
+struct App {
+    grid: Option<Grid<WasmRng>>,
+    painter: Painter,
+    backend: CanvasBackend
+}
+
+/// Translate a key press into the same grid operations the native backend binds
+/// to its keyboard.
+#[cfg(target_arch = "wasm32")]
+fn apply_key(app: &mut App, key: &str) {
+    let reset = |app: &mut App, make: fn(WasmRng) -> Grid<WasmRng>| {
+        let rng = app.grid.take().unwrap().into_rng();
+        app.grid = Some(make(rng));
+    };
+    match key {
+        " "       => { let g = app.grid.as_mut().unwrap(); g.running = !g.running; }
+        "1"       => reset(app, |r| Grid::new(r, 1.0)),
+        "2"       => reset(app, |r| Grid::new(r, 0.1)),
+        "3"       => reset(app, |r| Grid::new(r, 0.01)),
+        "0"       => reset(app, odd_grid),
+        "l" | "L" => reset(app, life),
+        "n" | "N" => reset(app, noise_grid),
+        "r" | "R" => { let g = app.grid.as_mut().unwrap(); g.show_regions = !g.show_regions; }
+        _         => {}
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn request_animation_frame(f: &Closure<dyn FnMut()>) {
+    web_sys::window().unwrap()
+        .request_animation_frame(f.as_ref().unchecked_ref())
+        .unwrap();
+}
+
+#[cfg(target_arch = "wasm32")]
+fn install_input_handlers(app: &Rc<RefCell<App>>, canvas: &web_sys::HtmlCanvasElement) {
+    {
+        let app = app.clone();
+        let handler = Closure::wrap(Box::new(move |e: web_sys::KeyboardEvent| {
+            apply_key(&mut app.borrow_mut(), &e.key());
+        }) as Box<dyn FnMut(_)>);
+        web_sys::window().unwrap()
+            .add_event_listener_with_callback("keydown", handler.as_ref().unchecked_ref())
+            .unwrap();
+        handler.forget();
+    }
+    {
+        let app = app.clone();
+        let handler = Closure::wrap(Box::new(move |e: web_sys::MouseEvent| {
+            let wall = e.button() == 2;
+            let mut a = app.borrow_mut();
+            let App { ref mut grid, ref mut painter, .. } = *a;
+            painter.press(grid.as_mut().unwrap(), e.offset_x() as f64, e.offset_y() as f64, wall);
+        }) as Box<dyn FnMut(_)>);
+        canvas.add_event_listener_with_callback("mousedown", handler.as_ref().unchecked_ref()).unwrap();
+        handler.forget();
+    }
+    {
+        let app = app.clone();
+        let handler = Closure::wrap(Box::new(move |e: web_sys::MouseEvent| {
+            let mut a = app.borrow_mut();
+            let App { ref mut grid, ref mut painter, .. } = *a;
+            painter.motion(grid.as_mut().unwrap(), e.offset_x() as f64, e.offset_y() as f64);
+        }) as Box<dyn FnMut(_)>);
+        canvas.add_event_listener_with_callback("mousemove", handler.as_ref().unchecked_ref()).unwrap();
+        handler.forget();
+    }
+    {
+        let app = app.clone();
+        let handler = Closure::wrap(Box::new(move |_: web_sys::MouseEvent| {
+            app.borrow_mut().painter.release();
+        }) as Box<dyn FnMut(_)>);
+        canvas.add_event_listener_with_callback("mouseup", handler.as_ref().unchecked_ref()).unwrap();
+        handler.forget();
+    }
+}
+
+/// Entry point when running in the browser. Finds the `<canvas id="canvas">`,
+/// wires up input, and starts the requestAnimationFrame loop.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(start)]
+pub fn start() -> Result<(), JsValue> {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let canvas = document.get_element_by_id("canvas").unwrap()
+        .dyn_into::<web_sys::HtmlCanvasElement>()?;
+    canvas.set_width(WINDOW_WIDTH);
+    canvas.set_height(WINDOW_HEIGHT);
+    let ctx = canvas.get_context("2d")?.unwrap()
+        .dyn_into::<web_sys::CanvasRenderingContext2d>()?;
+
+    let rng: WasmRng = rand::random();
+    let app = Rc::new(RefCell::new(App {
+        grid: Some(Grid::new(rng, 0.1)),
+        painter: Painter::new(),
+        backend: CanvasBackend {
+            ctx: ctx,
+            buf: vec![0; (WINDOW_WIDTH * WINDOW_HEIGHT * 4) as usize]
+        }
+    }));
+
+    install_input_handlers(&app, &canvas);
+
+    let raf = Rc::new(RefCell::new(None));
+    let raf2 = raf.clone();
+    *raf2.borrow_mut() = Some(Closure::wrap(Box::new(move || {
+        {
+            let mut a = app.borrow_mut();
+            let App { ref mut grid, ref mut backend, .. } = *a;
+            let grid = grid.as_mut().unwrap();
+            if grid.running {
+                grid.update(FRAME_DURATION);
+            }
+            backend.present(grid);
+        }
+        request_animation_frame(raf.borrow().as_ref().unwrap());
+    }) as Box<dyn FnMut()>));
+    request_animation_frame(raf2.borrow().as_ref().unwrap());
+    Ok(())
+}